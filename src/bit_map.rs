@@ -3,7 +3,38 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 use thiserror::Error;
-use crate::pixels::{Pallete, PixelArray};
+use crate::pixels::{DitherMethod, Pallete, Pixel, PixelArray};
+
+/// Bounds-checked little-endian reads over a byte slice, so a truncated or malformed file
+/// returns an `Issue` instead of panicking like a blind `bincode::deserialize(...).unwrap()`.
+trait BinUtil {
+    fn c_bytes(&self, off: usize, len: usize, field: &str) -> Result<&[u8], Issue>;
+    fn c_u16l(&self, off: usize, field: &str) -> Result<u16, Issue>;
+    fn c_u32l(&self, off: usize, field: &str) -> Result<u32, Issue>;
+    fn c_i32l(&self, off: usize, field: &str) -> Result<i32, Issue>;
+}
+
+impl BinUtil for [u8] {
+    fn c_bytes(&self, off: usize, len: usize, field: &str) -> Result<&[u8], Issue> {
+        self.get(off..off + len)
+            .ok_or_else(|| Issue::BadStruct(field.to_string(), (off + len).saturating_sub(self.len())))
+    }
+
+    fn c_u16l(&self, off: usize, field: &str) -> Result<u16, Issue> {
+        let bytes = self.c_bytes(off, 2, field)?;
+        Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn c_u32l(&self, off: usize, field: &str) -> Result<u32, Issue> {
+        let bytes = self.c_bytes(off, 4, field)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn c_i32l(&self, off: usize, field: &str) -> Result<i32, Issue> {
+        let bytes = self.c_bytes(off, 4, field)?;
+        Ok(i32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
 
 pub struct BitMap {
     file_header: BitMapFileHeader,
@@ -24,6 +55,12 @@ pub enum Issue {
 
     #[error("Unsupported format: {:?}", .0)]
     UnsupportedCompression(Compression),
+
+    #[error("Unsupported color depth: {0}")]
+    UnsupportedColorDepth(u16),
+
+    #[error("Unsupported BITMAPINFOHEADER size: {0}")]
+    UnsupportedHeaderSize(u32),
 }
 
 impl BitMap {
@@ -38,35 +75,71 @@ impl BitMap {
             return Err(Issue::IoError(err));
         }
 
-        if buffer.len() < 14 {
-            return Err(Issue::BadStruct("BitMapFileHeader".to_string(), 14 - buffer.len()));
-        }
-
-        let file_header: BitMapFileHeader = bincode::deserialize(&buffer[0..14]).unwrap();
+        let file_header = BitMapFileHeader {
+            identifier: buffer.c_bytes(0, 2, "BitMapFileHeader.identifier")?.try_into().unwrap(),
+            size: buffer.c_u32l(2, "BitMapFileHeader.size")?,
+            resevered: buffer.c_bytes(6, 4, "BitMapFileHeader.resevered")?.try_into().unwrap(),
+            offset: buffer.c_u32l(10, "BitMapFileHeader.offset")?,
+        };
 
-        let identifier:[u8; 2] = [0x42, 0x4d]; 
+        let identifier: [u8; 2] = [0x42, 0x4d];
         if !file_header.identifier.eq(&identifier) {
             return Err(Issue::ChecksumFailure(file_header.identifier[0], file_header.identifier[1]));
         }
 
-        if buffer.len() < 18 {
-            return Err(Issue::BadStruct("BitMapInfoHeader".to_string(), 18 - buffer.len()));
-        }
+        let bin_size = buffer.c_u32l(14, "BitMapInfoHeader.size")?;
 
-        let bin_size: u32 = bincode::deserialize(&buffer[14..18]).unwrap();
+        match bin_size {
+            40 | 108 | 124 => {},
+            other => return Err(Issue::UnsupportedHeaderSize(other)),
+        }
 
         if buffer.len() < 14 + bin_size as usize {
             return Err(Issue::BadStruct("BitMapInfoHeader".to_string(), (14 + bin_size as usize) - buffer.len()));
         }
 
-        let bin_header: BitMapInfoHeader = bincode::deserialize(&buffer[14..14 + bin_size as usize]).unwrap();
+        // Only the fields common to BITMAPINFOHEADER and its V4/V5 extensions are read;
+        // the color-mask/gamma/ICC-profile tail those add is unused by this pipeline.
+        let bin_header = BitMapInfoHeader {
+            size: bin_size,
+            pixel_width: buffer.c_i32l(18, "BitMapInfoHeader.pixel_width")?,
+            pixel_height: buffer.c_i32l(22, "BitMapInfoHeader.pixel_height")?,
+            color_planes: buffer.c_u16l(26, "BitMapInfoHeader.color_planes")?,
+            color_depth: buffer.c_u16l(28, "BitMapInfoHeader.color_depth")?,
+            compression: buffer.c_u32l(30, "BitMapInfoHeader.compression")?,
+            bitmap_size: buffer.c_u32l(34, "BitMapInfoHeader.bitmap_size")?,
+            width: buffer.c_i32l(38, "BitMapInfoHeader.width")?,
+            height: buffer.c_i32l(42, "BitMapInfoHeader.height")?,
+            colors: buffer.c_u32l(46, "BitMapInfoHeader.colors")?,
+            important_colors: buffer.c_u32l(50, "BitMapInfoHeader.important_colors")?,
+        };
 
-        match (bin_header.compression as i32).try_into() {
-            Ok(Compression::RGB) => {},
-            Ok(x) => return Err(Issue::UnsupportedCompression(x)),
-            Err(_) => return Err(Issue::UnsupportedCompression(Compression::Unknown)),
+        let compression: Compression = (bin_header.compression as i32).try_into()
+            .unwrap_or(Compression::Unknown);
+
+        match compression {
+            Compression::RGB | Compression::RLE8 | Compression::RLE4 => {},
+            other => return Err(Issue::UnsupportedCompression(other)),
         }
 
+        let width = bin_header.pixel_width as usize;
+        let height = bin_header.pixel_height.unsigned_abs() as usize;
+        let flip = bin_header.pixel_height > 0;
+
+        let color_table = if bin_header.color_depth <= 8 {
+            let color_table_start = 14 + bin_header.size as usize;
+            let num_colors = if bin_header.colors == 0 { 1usize << bin_header.color_depth } else { bin_header.colors as usize };
+            let color_table_end = color_table_start + num_colors * 4;
+
+            if buffer.len() < color_table_end {
+                return Err(Issue::BadStruct("ColorTable".to_string(), color_table_end - buffer.len()));
+            }
+
+            Some(Self::read_color_table(&buffer[color_table_start..color_table_end]))
+        } else {
+            None
+        };
+
         let pixel_array_start = file_header.offset as usize;
         let pixel_array_end = pixel_array_start + bin_header.bitmap_size as usize;
 
@@ -74,13 +147,27 @@ impl BitMap {
             return Err(Issue::BadStruct("PixelArray".to_string(), pixel_array_end - buffer.len()));
         }
 
-        let pixel_array = PixelArray::new(
-            bin_header.pixel_width as usize,
-            bin_header.pixel_height.abs() as usize,
-            &buffer[pixel_array_start..pixel_array_end],
-            bin_header.pixel_height > 0
-        );
-        
+        let data = &buffer[pixel_array_start..pixel_array_end];
+
+        let pixel_array = match (compression, bin_header.color_depth) {
+            (Compression::RGB, 24) => PixelArray::new(width, height, data, flip),
+            (Compression::RGB, depth @ (1 | 4 | 8)) => {
+                let palette = color_table.expect("color table required for indexed depth");
+                let indices = Self::decode_packed_indices(data, width, height, depth as usize);
+                PixelArray::from_indexed(width, height, &indices, &palette, flip)
+            },
+            (Compression::RLE8, depth) if depth <= 8 => {
+                let palette = color_table.expect("color table required for RLE8");
+                let indices = Self::decode_rle8(data, width, height);
+                PixelArray::from_indexed(width, height, &indices, &palette, flip)
+            },
+            (Compression::RLE4, depth) if depth <= 8 => {
+                let palette = color_table.expect("color table required for RLE4");
+                let indices = Self::decode_rle4(data, width, height);
+                PixelArray::from_indexed(width, height, &indices, &palette, flip)
+            },
+            (_, depth) => return Err(Issue::UnsupportedColorDepth(depth)),
+        };
 
         Ok(BitMap { file_header, bin_header, pixel_array })
     }
@@ -93,28 +180,44 @@ impl BitMap {
 
         let mut file_offset = 0;
 
-        let buf = bincode::serialize(&self.file_header).unwrap();
+        // Dithered output is always written back as uncompressed 24-bit RGB in a plain
+        // 40-byte BITMAPINFOHEADER, regardless of the depth/compression/header-size the
+        // source bitmap was decoded from, so the headers are normalized to match the bytes
+        // actually written below (a V4/V5 `size` here would make `offset` point past the
+        // pixel data we actually wrote, and a carried-over `bitmap_size` from a smaller
+        // source depth would make a re-read of this file truncate the pixel data).
+        let row_size = (self.bin_header.pixel_width as usize * 3).div_ceil(4) * 4;
+        let bitmap_size = (row_size * self.bin_header.pixel_height.unsigned_abs() as usize) as u32;
+
+        let out_header = BitMapInfoHeader {
+            size: 40,
+            color_depth: 24,
+            compression: Compression::RGB as u32,
+            bitmap_size,
+            colors: 0,
+            important_colors: 0,
+            ..self.bin_header
+        };
+
+        let out_file_header = BitMapFileHeader {
+            offset: 14 + out_header.size,
+            ..self.file_header
+        };
+
+        let buf = bincode::serialize(&out_file_header).unwrap();
 
         match file.write(&buf) {
             Err(why) => return Err(Issue::IoError(why)),
             Ok(size) => file_offset += size,
         }
 
-        let buf = bincode::serialize(&self.bin_header).unwrap();
+        let buf = bincode::serialize(&out_header).unwrap();
 
         match file.write(&buf) {
             Err(why) => return Err(Issue::IoError(why)),
             Ok(size) => file_offset += size,
         }
 
-        if self.file_header.offset as usize > file_offset {
-            let buf = vec![0; self.file_header.offset as usize - file_offset];
-            match file.write(&buf) {
-                Err(why) => return Err(Issue::IoError(why)),
-                Ok(size) => file_offset += size,
-            }
-        }
-
         let mut buf = Vec::new();
 
         for pixel in &self.pixel_array.pixel_array(self.bin_header.pixel_height > 0) {
@@ -140,13 +243,147 @@ impl BitMap {
     }
 
     
-    pub fn dither_floydsteinberg(&mut self, pallete: &Pallete, nbits: i32) {
-        self.pixel_array.dither_floydsteinberg(pallete, nbits);
+    pub fn dither(&mut self, pallete: &Pallete, nbits: i32, method: DitherMethod) {
+        self.pixel_array.dither(pallete, nbits, method);
+    }
+
+    pub fn pixel_array(&self) -> &PixelArray {
+        &self.pixel_array
+    }
+
+    fn read_color_table(raw: &[u8]) -> Vec<Pixel> {
+        raw.chunks(4).filter(|chunk| chunk.len() == 4)
+            .map(|chunk| Pixel { r: chunk[2], g: chunk[1], b: chunk[0] })
+            .collect()
+    }
+
+    /// Unpacks 1/4/8 bit-per-pixel indices out of rows padded to a 4-byte boundary.
+    fn decode_packed_indices(data: &[u8], width: usize, height: usize, depth: usize) -> Vec<u8> {
+        let row_size = (depth * width).div_ceil(32) * 4;
+        let mut indices = vec![0u8; width * height];
+
+        for y in 0..height {
+            let row = match data.get(y * row_size..(y + 1) * row_size) {
+                Some(row) => row,
+                None => break,
+            };
+
+            for x in 0..width {
+                let index = match depth {
+                    1 => (row[x / 8] >> (7 - (x % 8))) & 0b1,
+                    4 => {
+                        let byte = row[x / 2];
+                        if x % 2 == 0 { byte >> 4 } else { byte & 0x0F }
+                    },
+                    8 => row[x],
+                    _ => unreachable!("caller guarantees depth is 1, 4 or 8"),
+                };
+                indices[y * width + x] = index;
+            }
+        }
+
+        indices
+    }
+
+    /// Decodes an RLE8 stream per the Microsoft BMP spec into a dense `width * height` index grid.
+    fn decode_rle8(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let mut indices = vec![0u8; width * height];
+        let (mut x, mut y): (i32, i32) = (0, 0);
+        let mut i = 0;
+
+        while i + 1 < data.len() {
+            let count = data[i];
+            let op = data[i + 1];
+
+            if count > 0 {
+                i += 2;
+                for _ in 0..count {
+                    Self::put_index(&mut indices, width, height, x, y, op);
+                    x += 1;
+                }
+            } else {
+                match op {
+                    0 => { x = 0; y += 1; i += 2; },
+                    1 => break,
+                    2 => {
+                        let dx = *data.get(i + 2).unwrap_or(&0) as i32;
+                        let dy = *data.get(i + 3).unwrap_or(&0) as i32;
+                        x += dx;
+                        y += dy;
+                        i += 4;
+                    },
+                    run => {
+                        let run = run as usize;
+                        for n in 0..run {
+                            let value = *data.get(i + 2 + n).unwrap_or(&0);
+                            Self::put_index(&mut indices, width, height, x, y, value);
+                            x += 1;
+                        }
+                        i += 2 + run + (run % 2);
+                    },
+                }
+            }
+        }
+
+        indices
+    }
+
+    /// Decodes an RLE4 stream, where each value byte packs two 4-bit indices.
+    fn decode_rle4(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let mut indices = vec![0u8; width * height];
+        let (mut x, mut y): (i32, i32) = (0, 0);
+        let mut i = 0;
+
+        while i + 1 < data.len() {
+            let count = data[i];
+            let op = data[i + 1];
+
+            if count > 0 {
+                i += 2;
+                for n in 0..count {
+                    let value = if n % 2 == 0 { op >> 4 } else { op & 0x0F };
+                    Self::put_index(&mut indices, width, height, x, y, value);
+                    x += 1;
+                }
+            } else {
+                match op {
+                    0 => { x = 0; y += 1; i += 2; },
+                    1 => break,
+                    2 => {
+                        let dx = *data.get(i + 2).unwrap_or(&0) as i32;
+                        let dy = *data.get(i + 3).unwrap_or(&0) as i32;
+                        x += dx;
+                        y += dy;
+                        i += 4;
+                    },
+                    run => {
+                        let run = run as usize;
+                        let packed_bytes = run.div_ceil(2);
+                        for n in 0..run {
+                            let byte = *data.get(i + 2 + n / 2).unwrap_or(&0);
+                            let value = if n % 2 == 0 { byte >> 4 } else { byte & 0x0F };
+                            Self::put_index(&mut indices, width, height, x, y, value);
+                            x += 1;
+                        }
+                        i += 2 + packed_bytes + (packed_bytes % 2);
+                    },
+                }
+            }
+        }
+
+        indices
+    }
+
+    fn put_index(indices: &mut [u8], width: usize, height: usize, x: i32, y: i32, value: u8) {
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            return;
+        }
+        indices[y as usize * width + x as usize] = value;
     }
     }
 
     
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
 struct BitMapFileHeader {
     identifier: [u8; 2],
     size: u32,
@@ -154,7 +391,7 @@ struct BitMapFileHeader {
     offset: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Compression {
     RGB = 0,
     RLE8 = 1,
@@ -190,7 +427,7 @@ impl TryFrom<i32> for Compression {
     }
 } 
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
 struct BitMapInfoHeader {
     size: u32, // Size of header in bytes (should be 40)
     pixel_width: i32,
@@ -1,16 +1,20 @@
 pub mod pixels;
 pub mod bit_map;
+pub mod png;
+pub mod tiff;
 
 pub mod config {
     use clap::Parser;
     use thiserror::Error;
     use std::str::FromStr;
 
-    use crate::pixels::Pallete;
+    use crate::pixels::{self, DitherMethod, Pallete, PixelArray};
 
     #[derive(Debug)]
     pub enum Extension {
         BMP,
+        Png,
+        Tiff,
     }
 
     #[derive(Error, Debug)]
@@ -31,6 +35,8 @@ pub mod config {
             if let Some(extension) = extension {
                 let extension: Extension = match extension {
                     "bmp" => { Extension::BMP },
+                    "png" => { Extension::Png },
+                    "tiff" | "tif" => { Extension::Tiff },
                     _ => { return Err(Issue::UnknownExtension(extension.to_string())) },
                 };
                 return Ok(extension);
@@ -50,15 +56,31 @@ pub mod config {
 
         /// HTML basic colors seperated by commas: "red, green, blue"
         #[clap(short, long)]
-        pub color_string: String,
-       
+        pub color_string: Option<String>,
+
         /// Bits per color
         #[clap(short, long)]
-        pub bits: u8,
+        pub bits: i32,
 
         /// Path of output file
         #[clap(short, long)]
         pub output: std::path::PathBuf,
+
+        /// Palette source: "named" uses --color-string, "auto" derives a palette from the image via median-cut
+        #[clap(long, default_value = "named")]
+        pub palette: String,
+
+        /// Number of colors to generate when --palette auto is used
+        #[clap(long, default_value_t = 16)]
+        pub colors: usize,
+
+        /// Path to a palette file (one color per line, "#rrggbb" or "R G B") to use instead of --color-string
+        #[clap(long)]
+        pub palette_file: Option<std::path::PathBuf>,
+
+        /// Dithering algorithm: floyd-steinberg, jarvis, stucki, atkinson, or ordered
+        #[clap(long, default_value = "floyd-steinberg")]
+        pub dither_method: String,
     }
 
     impl Config {
@@ -74,14 +96,28 @@ pub mod config {
             Err(Issue::InvalidExtension)     
         }
 
-        pub fn pallete(&self) -> Pallete {
-            let colors: Vec<String> = self.color_string.split(',')
+        pub fn pallete(&self, pixel_array: &PixelArray) -> Result<Pallete, pixels::Issue> {
+            if let Some(palette_file) = &self.palette_file {
+                return Pallete::from_file(palette_file);
+            }
+
+            if self.palette == "auto" {
+                return Pallete::from_median_cut(pixel_array, self.colors);
+            }
+
+            let color_string = self.color_string.clone().unwrap_or_default();
+
+            let colors: Vec<String> = color_string.split(',')
             .map(str::trim).map(str::to_ascii_lowercase).collect();
 
             let color_ref: Vec<&str> = colors.iter().map(|x| x.as_ref()).collect();
 
             Pallete::new(&color_ref)
         }
+
+        pub fn dither_method(&self) -> Result<DitherMethod, pixels::Issue> {
+            self.dither_method.parse()
+        }
     }
 }
 
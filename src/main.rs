@@ -5,6 +5,8 @@ use clap::Parser;
 use floyd_dithering::config::Config;
 use floyd_dithering::config::Extension;
 use floyd_dithering::bit_map::*;
+use floyd_dithering::png::PngImage;
+use floyd_dithering::tiff::TiffImage;
 
 fn main() {
     
@@ -15,6 +17,11 @@ fn main() {
         process::exit(1);
     });
 
+    let dither_method = config.dither_method().unwrap_or_else(|err| {
+        eprintln!("Error while processing dither method: {}", err);
+        process::exit(1);
+    });
+
     match ext {
         Extension::BMP => {
             let mut bit_map = BitMap::new(&config.filename).unwrap_or_else(|err| {
@@ -22,10 +29,13 @@ fn main() {
                 process::exit(1);
             });
 
-            let pallete = config.pallete();
+            let pallete = config.pallete(bit_map.pixel_array()).unwrap_or_else(|err| {
+                eprintln!("Error while building palette: {}", err);
+                process::exit(1);
+            });
 
             // Transform the image
-            bit_map.dither_floydsteinberg(&pallete, config.bits);
+            bit_map.dither(&pallete, config.bits, dither_method);
 
             let size = bit_map.save(config.output.as_path()).unwrap_or_else(|err| {
                 eprintln!("Error while saving bitmap file: {}", err);
@@ -36,7 +46,55 @@ fn main() {
                 Some(filename) => { filename },
                 None => {""}
             });
-            
+
+        }
+        Extension::Png => {
+            let mut png = PngImage::new(&config.filename).unwrap_or_else(|err| {
+                eprintln!("Error while processing png file: {}", err);
+                process::exit(1);
+            });
+
+            let pallete = config.pallete(png.pixel_array()).unwrap_or_else(|err| {
+                eprintln!("Error while building palette: {}", err);
+                process::exit(1);
+            });
+
+            // Transform the image
+            png.dither(&pallete, config.bits, dither_method);
+
+            let size = png.save(config.output.as_path()).unwrap_or_else(|err| {
+                eprintln!("Error while saving png file: {}", err);
+                process::exit(1);
+            });
+
+            println!("Wrote {} bytes to {}", size, match config.output.to_str() {
+                Some(filename) => { filename },
+                None => {""}
+            });
+        }
+        Extension::Tiff => {
+            let mut tiff = TiffImage::new(&config.filename).unwrap_or_else(|err| {
+                eprintln!("Error while processing tiff file: {}", err);
+                process::exit(1);
+            });
+
+            let pallete = config.pallete(tiff.pixel_array()).unwrap_or_else(|err| {
+                eprintln!("Error while building palette: {}", err);
+                process::exit(1);
+            });
+
+            // Transform the image
+            tiff.dither(&pallete, config.bits, dither_method);
+
+            let size = tiff.save(config.output.as_path()).unwrap_or_else(|err| {
+                eprintln!("Error while saving tiff file: {}", err);
+                process::exit(1);
+            });
+
+            println!("Wrote {} bytes to {}", size, match config.output.to_str() {
+                Some(filename) => { filename },
+                None => {""}
+            });
         }
     }
 
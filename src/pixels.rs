@@ -1,4 +1,23 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Issue {
+    #[error("Error reading palette file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Could not resolve color: {0}")]
+    UnknownColor(String),
+
+    #[error("Unknown dither method: {0}")]
+    UnknownDitherMethod(String),
+
+    #[error("Cannot dither with an empty palette")]
+    EmptyPalette,
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
 pub struct Pixel {
@@ -121,9 +140,48 @@ pub struct Pallete {
 }
 
 impl Pallete {
-    pub fn new(color_names: &[&str]) -> Pallete {
+    /// Resolves each entry against the named colors, a `#rrggbb`/`rrggbb` hex triplet, or a
+    /// space-separated `r g b` triplet. Returns an error instead of silently dropping an
+    /// entry that resolves to none of the above, so a typo doesn't quietly shrink the palette.
+    pub fn new(color_names: &[&str]) -> Result<Pallete, Issue> {
+        let color_map = Pallete::named_colors();
+
+        let colors = color_names.iter()
+            .map(|color| Pallete::resolve_color(color, &color_map))
+            .collect::<Result<Vec<Pixel>, Issue>>()?;
 
-        // Don't know how to build this statically
+        Pallete::from_colors(colors)
+    }
+
+    /// Loads a palette from a file, one color per line (`#rrggbb` or `R G B`), ignoring blank
+    /// lines and `//`-prefixed comments.
+    pub fn from_file(path: &Path) -> Result<Pallete, Issue> {
+        let file = File::open(path)?;
+        let color_map = Pallete::named_colors();
+
+        let colors = BufReader::new(file).lines()
+            .collect::<Result<Vec<String>, std::io::Error>>()?
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(|line| Pallete::resolve_color(&line.to_ascii_lowercase(), &color_map))
+            .collect::<Result<Vec<Pixel>, Issue>>()?;
+
+        Pallete::from_colors(colors)
+    }
+
+    /// Wraps `colors` into a `Pallete`, rejecting an empty result so a palette with nothing to
+    /// quantize against never reaches `quantize_rgb_pallete`'s `pallete[closest_index]` indexing.
+    fn from_colors(colors: Vec<Pixel>) -> Result<Pallete, Issue> {
+        if colors.is_empty() {
+            return Err(Issue::EmptyPalette);
+        }
+
+        Ok(Pallete { colors })
+    }
+
+    // Don't know how to build this statically
+    fn named_colors() -> HashMap<&'static str, Pixel> {
         let mut color_map = HashMap::new();
 
         color_map.insert("red", Pixel::new(255, 0, 0));
@@ -132,17 +190,215 @@ impl Pallete {
         color_map.insert("white", Pixel::new(255, 255, 255));
         color_map.insert("black", Pixel::new(0, 0, 0));
 
-        let mut colors = Vec::new();
-        for color in color_names {
-            if let Some(color_pixel) = color_map.get(color) {
-                colors.push(color_pixel.clone());
-            }
+        color_map
+    }
+
+    fn resolve_color(token: &str, color_map: &HashMap<&str, Pixel>) -> Result<Pixel, Issue> {
+        if let Some(pixel) = color_map.get(token) {
+            return Ok(*pixel);
+        }
+
+        if let Some(pixel) = Pallete::parse_hex(token) {
+            return Ok(pixel);
+        }
+
+        if let Some(pixel) = Pallete::parse_triplet(token) {
+            return Ok(pixel);
+        }
+
+        Err(Issue::UnknownColor(token.to_string()))
+    }
+
+    fn parse_hex(token: &str) -> Option<Pixel> {
+        let hex = token.strip_prefix('#').unwrap_or(token);
+
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
         }
 
-        Pallete { colors }
+        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+
+        Some(Pixel::new(r, g, b))
+    }
+
+    fn parse_triplet(token: &str) -> Option<Pixel> {
+        let parts: Vec<&str> = token.split_whitespace().collect();
+
+        if parts.len() != 3 {
+            return None;
+        }
+
+        let r = parts[0].parse().ok()?;
+        let g = parts[1].parse().ok()?;
+        let b = parts[2].parse().ok()?;
+
+        Some(Pixel::new(r, g, b))
+    }
+
+    /// Derives an `num_colors`-entry palette straight from the image via median-cut
+    /// quantization, rather than relying on the small set of named colors. Errors instead of
+    /// producing an empty palette, which would later panic when dithering indexes into it.
+    pub fn from_median_cut(pixel_array: &PixelArray, num_colors: usize) -> Result<Pallete, Issue> {
+        let pixels = pixel_array.pixel_array(false);
+
+        if pixels.is_empty() || num_colors == 0 {
+            return Err(Issue::EmptyPalette);
+        }
+
+        let mut boxes: Vec<Vec<Pixel>> = vec![pixels];
+
+        while boxes.len() < num_colors {
+            let split_target = boxes.iter().enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .map(|(i, b)| {
+                    let (axis, range) = Pallete::longest_axis(b);
+                    (i, axis, range)
+                })
+                .max_by_key(|&(_, _, range)| range);
+
+            let (index, axis, _) = match split_target {
+                Some(target) => target,
+                None => break,
+            };
+
+            let box_pixels = boxes.swap_remove(index);
+            let (a, b) = Pallete::split_box(box_pixels, axis);
+            boxes.push(a);
+            boxes.push(b);
+        }
+
+        let colors = boxes.iter().map(|b| Pallete::mean_color(b)).collect();
+
+        Pallete::from_colors(colors)
+    }
+
+    /// Finds the RGB channel (and its value range) with the widest spread in `pixels`.
+    fn longest_axis(pixels: &[Pixel]) -> (Axis, u32) {
+        let (mut r_min, mut r_max) = (255u8, 0u8);
+        let (mut g_min, mut g_max) = (255u8, 0u8);
+        let (mut b_min, mut b_max) = (255u8, 0u8);
+
+        for p in pixels {
+            r_min = r_min.min(p.r); r_max = r_max.max(p.r);
+            g_min = g_min.min(p.g); g_max = g_max.max(p.g);
+            b_min = b_min.min(p.b); b_max = b_max.max(p.b);
+        }
+
+        let ranges = [
+            (Axis::R, (r_max - r_min) as u32),
+            (Axis::G, (g_max - g_min) as u32),
+            (Axis::B, (b_max - b_min) as u32),
+        ];
+
+        *ranges.iter().max_by_key(|(_, range)| *range).unwrap()
+    }
+
+    /// Sorts `pixels` along `axis` and splits the run at its median.
+    fn split_box(mut pixels: Vec<Pixel>, axis: Axis) -> (Vec<Pixel>, Vec<Pixel>) {
+        pixels.sort_by_key(|p| axis.value(p));
+        let mid = pixels.len() / 2;
+        let tail = pixels.split_off(mid);
+        (pixels, tail)
+    }
+
+    fn mean_color(pixels: &[Pixel]) -> Pixel {
+        let len = pixels.len() as u32;
+        let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+
+        for p in pixels {
+            r += p.r as u32;
+            g += p.g as u32;
+            b += p.b as u32;
+        }
+
+        Pixel { r: (r / len) as u8, g: (g / len) as u8, b: (b / len) as u8 }
     }
 }
 
+#[derive(Clone, Copy)]
+enum Axis { R, G, B }
+
+impl Axis {
+    fn value(&self, pixel: &Pixel) -> u8 {
+        match self {
+            Axis::R => pixel.r,
+            Axis::G => pixel.g,
+            Axis::B => pixel.b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherMethod {
+    FloydSteinberg,
+    Jarvis,
+    Stucki,
+    Atkinson,
+    Ordered,
+}
+
+impl std::str::FromStr for DitherMethod {
+    type Err = Issue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "floyd-steinberg" | "floydsteinberg" => Ok(DitherMethod::FloydSteinberg),
+            "jarvis" => Ok(DitherMethod::Jarvis),
+            "stucki" => Ok(DitherMethod::Stucki),
+            "atkinson" => Ok(DitherMethod::Atkinson),
+            "ordered" => Ok(DitherMethod::Ordered),
+            other => Err(Issue::UnknownDitherMethod(other.to_string())),
+        }
+    }
+}
+
+/// An error-diffusion kernel: a divisor plus `(dx, dy, weight)` offsets applied to
+/// not-yet-visited neighbors. `weight` is the numerator over `divisor`.
+struct Kernel {
+    divisor: i32,
+    offsets: &'static [(i32, i32, i32)],
+}
+
+const FLOYD_STEINBERG: Kernel = Kernel {
+    divisor: 16,
+    offsets: &[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)],
+};
+
+const JARVIS: Kernel = Kernel {
+    divisor: 48,
+    offsets: &[
+        (1, 0, 7), (2, 0, 5),
+        (-2, 1, 3), (-1, 1, 5), (0, 1, 7), (1, 1, 5), (2, 1, 3),
+        (-2, 2, 1), (-1, 2, 3), (0, 2, 5), (1, 2, 3), (2, 2, 1),
+    ],
+};
+
+const STUCKI: Kernel = Kernel {
+    divisor: 42,
+    offsets: &[
+        (1, 0, 8), (2, 0, 4),
+        (-2, 1, 2), (-1, 1, 4), (0, 1, 8), (1, 1, 4), (2, 1, 2),
+        (-2, 2, 1), (-1, 2, 2), (0, 2, 4), (1, 2, 2), (2, 2, 1),
+    ],
+};
+
+// Only distributes 6/8 of the error; the rest is dropped for Atkinson's characteristic
+// higher-contrast look.
+const ATKINSON: Kernel = Kernel {
+    divisor: 8,
+    offsets: &[(1, 0, 1), (2, 0, 1), (-1, 1, 1), (0, 1, 1), (1, 1, 1), (0, 2, 1)],
+};
+
+// Recursively-defined 4x4 Bayer threshold matrix.
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
 pub struct PixelArray {
     pixel_array: Vec<Pixel>,
     width: usize,
@@ -169,6 +425,35 @@ impl PixelArray {
             PixelArray { pixel_array, width, height }
     }
 
+    /// Builds a `PixelArray` from rows of already-decoded, top-down RGB(A) bytes,
+    /// dropping the alpha channel when `channels` is 4.
+    pub fn from_rgb_rows(width: usize, height: usize, raw_pixels: &[u8], channels: usize) -> PixelArray {
+        let mut pixel_array: Vec<Pixel> = Vec::with_capacity(width * height);
+
+        for chunk in raw_pixels.chunks(channels) {
+            if chunk.len() != channels {
+                break;
+            }
+            pixel_array.push(Pixel { r: chunk[0], g: chunk[1], b: chunk[2] });
+        }
+
+        PixelArray { pixel_array, width, height }
+    }
+
+    /// Resolves a dense grid of palette indices (as produced by paletted or RLE BMP decoding)
+    /// into concrete pixels.
+    pub fn from_indexed(width: usize, height: usize, indices: &[u8], palette: &[Pixel], flip: bool) -> PixelArray {
+        let mut pixel_array: Vec<Pixel> = indices.iter()
+            .map(|&index| *palette.get(index as usize).unwrap_or(&Pixel::new(0, 0, 0)))
+            .collect();
+
+        if flip {
+            pixel_array = PixelArray::flip(width, height, &pixel_array);
+        }
+
+        PixelArray { pixel_array, width, height }
+    }
+
     fn flip(width: usize, height: usize, pixel_array: &Vec<Pixel>) -> Vec<Pixel> {
         let mut flipped = vec![Pixel::new(0, 0, 0); pixel_array.len()];
         for y in (0..(height - 1) as usize).rev() {
@@ -210,7 +495,17 @@ impl PixelArray {
         self.pixel_array.clone()
     }
 
-    pub fn dither_floydsteinberg(&mut self, pallete: &Pallete, nbits: i32) {
+    pub fn dither(&mut self, pallete: &Pallete, nbits: i32, method: DitherMethod) {
+        match method {
+            DitherMethod::FloydSteinberg => self.diffuse(pallete, nbits, &FLOYD_STEINBERG),
+            DitherMethod::Jarvis => self.diffuse(pallete, nbits, &JARVIS),
+            DitherMethod::Stucki => self.diffuse(pallete, nbits, &STUCKI),
+            DitherMethod::Atkinson => self.diffuse(pallete, nbits, &ATKINSON),
+            DitherMethod::Ordered => self.dither_ordered(pallete, nbits),
+        }
+    }
+
+    fn diffuse(&mut self, pallete: &Pallete, nbits: i32, kernel: &Kernel) {
         let height: i32 = self.height.try_into().unwrap();
         let width: i32 = self.width.try_into().unwrap();
         for y in 0..(height - 1) {
@@ -218,37 +513,57 @@ impl PixelArray {
                 let original = self.get_pixel(x, y);
                 let quantized = original.quantize_rgb_nbit(pallete, nbits);
 
-                let error:[i32; 3] = [
-                    (original.r as i32 - quantized.r as i32), 
-                    (original.g as i32 - quantized.g as i32), 
+                let error: [i32; 3] = [
+                    (original.r as i32 - quantized.r as i32),
+                    (original.g as i32 - quantized.g as i32),
                     (original.b as i32 - quantized.b as i32)
-                    ];
-                
+                ];
+
                 self.set_pixel(x, y, quantized);
 
-                // Update the corresponding pixels surrounding the current one
-                let mut update_pixel = | offset: (i32, i32), error_bias: f32 | {
-                    let x = x + offset.0;
-                    let y = y + offset.1;
-                    let pixel = self.get_pixel(x, y);
-                    
-                    let mut k = [pixel.r as i32, pixel.g as i32, pixel.b as i32];
-                    k[0] += (error[0] as f32 * error_bias) as i32;
-                    k[1] += (error[1] as f32 * error_bias) as i32;
-                    k[2] += (error[2] as f32 * error_bias) as i32;
-
-                    let pixel = Pixel { 
+                // Spread the quantization error to not-yet-visited neighbors
+                for &(dx, dy, weight) in kernel.offsets {
+                    let bias = weight as f32 / kernel.divisor as f32;
+                    let pixel = self.get_pixel(x + dx, y + dy);
+
+                    let k = [
+                        pixel.r as i32 + (error[0] as f32 * bias) as i32,
+                        pixel.g as i32 + (error[1] as f32 * bias) as i32,
+                        pixel.b as i32 + (error[2] as f32 * bias) as i32,
+                    ];
+
+                    let pixel = Pixel {
                         r: k[0].clamp(0, 255) as u8,
                         g: k[1].clamp(0, 255) as u8,
-                        b: k[2].clamp(0, 255) as u8 
+                        b: k[2].clamp(0, 255) as u8
                     };
-                    self.set_pixel(x, y, pixel);
+                    self.set_pixel(x + dx, y + dy, pixel);
+                }
+            }
+        }
+    }
+
+    /// Ordered dithering: no error propagation, so every pixel can be quantized
+    /// independently. Adds a per-pixel bias from a tiled Bayer matrix before quantizing.
+    fn dither_ordered(&mut self, pallete: &Pallete, nbits: i32) {
+        let height: i32 = self.height.try_into().unwrap();
+        let width: i32 = self.width.try_into().unwrap();
+        let levels = ((1 << nbits) - 1) as f32;
+        let amplitude = 255.0 / levels;
+
+        for y in 0..height {
+            for x in 0..width {
+                let threshold = (BAYER_4X4[(y as usize) % 4][(x as usize) % 4] as f32 / 16.0 - 0.5) * amplitude;
+
+                let original = self.get_pixel(x, y);
+                let biased = Pixel {
+                    r: (original.r as f32 + threshold).clamp(0.0, 255.0) as u8,
+                    g: (original.g as f32 + threshold).clamp(0.0, 255.0) as u8,
+                    b: (original.b as f32 + threshold).clamp(0.0, 255.0) as u8,
                 };
 
-                update_pixel((1, 0), 7.0f32 / 16.0f32);
-                update_pixel((-1, 1), 3.0f32 / 16.0f32);
-                update_pixel((0, 1), 5.0f32 / 16.0f32);
-                update_pixel((1, 1), 1.0f32 / 16.0f32);
+                let quantized = biased.quantize_rgb_nbit(pallete, nbits);
+                self.set_pixel(x, y, quantized);
             }
         }
     }
@@ -0,0 +1,313 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use thiserror::Error;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression as ZlibCompression;
+
+use crate::pixels::{DitherMethod, Pallete, PixelArray};
+
+const SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+#[derive(Error, Debug)]
+pub enum Issue {
+    #[error("Error reading file {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Bad {0}, missing {1} bytes")]
+    BadStruct(String, usize),
+
+    #[error("Bad PNG signature: {0:x?}")]
+    BadSignature(Vec<u8>),
+
+    #[error("CRC mismatch in {0} chunk: found {1:x}, expected {2:x}")]
+    CrcMismatch(String, u32, u32),
+
+    #[error("Unsupported color type: {0}")]
+    UnsupportedColorType(u8),
+
+    #[error("Unsupported bit depth: {0}")]
+    UnsupportedBitDepth(u8),
+
+    #[error("Missing IHDR chunk")]
+    MissingIhdr,
+
+    #[error("Missing IDAT data")]
+    MissingIdat,
+
+    #[error("Failed to inflate IDAT stream: {0}")]
+    InflateFailed(std::io::Error),
+
+    #[error("Unsupported filter type: {0}")]
+    UnsupportedFilter(u8),
+}
+
+pub struct PngImage {
+    width: u32,
+    height: u32,
+    pixel_array: PixelArray,
+}
+
+struct Ihdr {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+struct Chunk<'a> {
+    kind: [u8; 4],
+    data: &'a [u8],
+}
+
+impl PngImage {
+    pub fn new(filename: &Path) -> Result<PngImage, Issue> {
+        let mut file = File::open(filename)?;
+
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+
+        if buffer.len() < SIGNATURE.len() {
+            return Err(Issue::BadStruct("signature".to_string(), SIGNATURE.len() - buffer.len()));
+        }
+
+        if buffer[0..8] != SIGNATURE {
+            return Err(Issue::BadSignature(buffer[0..8].to_vec()));
+        }
+
+        let mut ihdr: Option<Ihdr> = None;
+        let mut idat = Vec::new();
+
+        let mut offset = 8;
+        while offset < buffer.len() {
+            let chunk = Self::read_chunk(&buffer, offset)?;
+            offset += 12 + chunk.data.len();
+
+            match &chunk.kind {
+                b"IHDR" => ihdr = Some(Self::parse_ihdr(chunk.data)?),
+                b"IDAT" => idat.extend_from_slice(chunk.data),
+                b"IEND" => break,
+                _ => {}
+            }
+        }
+
+        let ihdr = ihdr.ok_or(Issue::MissingIhdr)?;
+
+        if idat.is_empty() {
+            return Err(Issue::MissingIdat);
+        }
+
+        if ihdr.bit_depth != 8 {
+            return Err(Issue::UnsupportedBitDepth(ihdr.bit_depth));
+        }
+
+        let channels = match ihdr.color_type {
+            2 => 3, // RGB
+            6 => 4, // RGBA
+            other => return Err(Issue::UnsupportedColorType(other)),
+        };
+
+        let mut inflated = Vec::new();
+        ZlibDecoder::new(&idat[..])
+            .read_to_end(&mut inflated)
+            .map_err(Issue::InflateFailed)?;
+
+        let raw = Self::unfilter(&inflated, ihdr.width as usize, ihdr.height as usize, channels)?;
+
+        let pixel_array = PixelArray::from_rgb_rows(
+            ihdr.width as usize,
+            ihdr.height as usize,
+            &raw,
+            channels,
+        );
+
+        Ok(PngImage { width: ihdr.width, height: ihdr.height, pixel_array })
+    }
+
+    fn read_chunk(buffer: &[u8], offset: usize) -> Result<Chunk<'_>, Issue> {
+        let header = buffer.get(offset..offset + 8)
+            .ok_or_else(|| Issue::BadStruct("chunk header".to_string(), offset + 8 - buffer.len()))?;
+
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind: [u8; 4] = [header[4], header[5], header[6], header[7]];
+
+        let data = buffer.get(offset + 8..offset + 8 + length)
+            .ok_or_else(|| Issue::BadStruct("chunk data".to_string(), offset + 8 + length - buffer.len()))?;
+
+        let crc_bytes = buffer.get(offset + 8 + length..offset + 12 + length)
+            .ok_or_else(|| Issue::BadStruct("chunk crc".to_string(), offset + 12 + length - buffer.len()))?;
+        let crc = u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]);
+
+        let mut crc_input = Vec::with_capacity(4 + length);
+        crc_input.extend_from_slice(&kind);
+        crc_input.extend_from_slice(data);
+        let computed = crc32(&crc_input);
+
+        if computed != crc {
+            return Err(Issue::CrcMismatch(String::from_utf8_lossy(&kind).to_string(), computed, crc));
+        }
+
+        Ok(Chunk { kind, data })
+    }
+
+    fn parse_ihdr(data: &[u8]) -> Result<Ihdr, Issue> {
+        if data.len() < 13 {
+            return Err(Issue::BadStruct("IHDR".to_string(), 13 - data.len()));
+        }
+
+        let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        let bit_depth = data[8];
+        let color_type = data[9];
+
+        Ok(Ihdr { width, height, bit_depth, color_type })
+    }
+
+    fn unfilter(data: &[u8], width: usize, height: usize, channels: usize) -> Result<Vec<u8>, Issue> {
+        let stride = width * channels;
+
+        // IHDR's width/height are attacker-controlled; validate the inflated stream is big
+        // enough to hold them (one filter-type byte plus `stride` bytes per row) before
+        // allocating `out`, instead of risking a multiply overflow or a tiny wrapped
+        // allocation followed by an out-of-bounds panic below.
+        let required = stride.checked_add(1)
+            .and_then(|row_len| row_len.checked_mul(height))
+            .ok_or_else(|| Issue::BadStruct("IHDR dimensions".to_string(), 0))?;
+
+        if data.len() < required {
+            return Err(Issue::BadStruct("inflated IDAT stream".to_string(), required - data.len()));
+        }
+
+        let mut out = vec![0u8; stride * height];
+
+        for y in 0..height {
+            let row_start = y * (stride + 1);
+            let filter = *data.get(row_start)
+                .ok_or_else(|| Issue::BadStruct("scanline".to_string(), row_start + 1 - data.len()))?;
+
+            let src = data.get(row_start + 1..row_start + 1 + stride)
+                .ok_or_else(|| Issue::BadStruct("scanline".to_string(), row_start + 1 + stride - data.len()))?;
+
+            let (prev, cur) = out.split_at_mut(y * stride);
+            let cur = &mut cur[..stride];
+            let prior = if y == 0 { None } else { Some(&prev[(y - 1) * stride..y * stride]) };
+
+            for i in 0..stride {
+                let a = if i >= channels { cur[i - channels] } else { 0 };
+                let b = prior.map_or(0, |p| p[i]);
+                let c = if i >= channels { prior.map_or(0, |p| p[i - channels]) } else { 0 };
+
+                let recon = match filter {
+                    0 => src[i],
+                    1 => src[i].wrapping_add(a),
+                    2 => src[i].wrapping_add(b),
+                    3 => src[i].wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                    4 => src[i].wrapping_add(paeth(a, b, c)),
+                    other => return Err(Issue::UnsupportedFilter(other)),
+                };
+
+                cur[i] = recon;
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<usize, Issue> {
+        let mut file = File::create(path)?;
+
+        let mut written = file.write(&SIGNATURE)?;
+
+        let mut ihdr_data = Vec::with_capacity(13);
+        ihdr_data.extend_from_slice(&self.width.to_be_bytes());
+        ihdr_data.extend_from_slice(&self.height.to_be_bytes());
+        ihdr_data.push(8); // bit depth
+        ihdr_data.push(2); // color type: RGB
+        ihdr_data.push(0); // compression
+        ihdr_data.push(0); // filter
+        ihdr_data.push(0); // interlace
+        written += write_chunk(&mut file, b"IHDR", &ihdr_data)?;
+
+        let mut raw = Vec::with_capacity(self.height as usize * (1 + self.width as usize * 3));
+        for pixel in self.pixel_array.pixel_array(false) {
+            raw.push(pixel.r);
+            raw.push(pixel.g);
+            raw.push(pixel.b);
+        }
+
+        let stride = self.width as usize * 3;
+        let mut filtered = Vec::with_capacity(raw.len() + self.height as usize);
+        for row in raw.chunks(stride) {
+            filtered.push(0); // filter type None
+            filtered.extend_from_slice(row);
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), ZlibCompression::default());
+        encoder.write_all(&filtered)?;
+        let compressed = encoder.finish()?;
+
+        written += write_chunk(&mut file, b"IDAT", &compressed)?;
+        written += write_chunk(&mut file, b"IEND", &[])?;
+
+        Ok(written)
+    }
+
+    pub fn dither(&mut self, pallete: &Pallete, nbits: i32, method: DitherMethod) {
+        self.pixel_array.dither(pallete, nbits, method);
+    }
+
+    pub fn pixel_array(&self) -> &PixelArray {
+        &self.pixel_array
+    }
+}
+
+fn write_chunk(file: &mut File, kind: &[u8; 4], data: &[u8]) -> Result<usize, Issue> {
+    let mut crc_input = Vec::with_capacity(4 + data.len());
+    crc_input.extend_from_slice(kind);
+    crc_input.extend_from_slice(data);
+
+    let mut written = file.write(&(data.len() as u32).to_be_bytes())?;
+    written += file.write(kind)?;
+    written += file.write(data)?;
+    written += file.write(&crc32(&crc_input).to_be_bytes())?;
+
+    Ok(written)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i32 + b as i32 - c as i32;
+    let pa = (p - a as i32).abs();
+    let pb = (p - b as i32).abs();
+    let pc = (p - c as i32).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let table = crc32_table();
+    let mut c: u32 = 0xFFFFFFFF;
+    for &byte in data {
+        c = table[((c ^ byte as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    c ^ 0xFFFFFFFF
+}
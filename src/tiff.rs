@@ -0,0 +1,428 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use thiserror::Error;
+
+use flate2::read::ZlibDecoder;
+
+use crate::pixels::PixelArray;
+
+#[derive(Error, Debug)]
+pub enum Issue {
+    #[error("Error reading file {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Bad {0}, missing {1} bytes")]
+    BadStruct(String, usize),
+
+    #[error("Unrecognized byte-order marker: {0:x?}")]
+    BadByteOrder(Vec<u8>),
+
+    #[error("Bad TIFF magic number: {0}")]
+    BadMagic(u16),
+
+    #[error("Missing required tag: {0}")]
+    MissingTag(&'static str),
+
+    #[error("Unsupported compression scheme: {0}")]
+    UnsupportedCompression(u32),
+
+    #[error("Unsupported bits per sample: {0}")]
+    UnsupportedBitsPerSample(u32),
+
+    #[error("Unsupported pixel format: {0} samples per pixel")]
+    UnsupportedSamplesPerPixel(usize),
+
+    #[error("Mismatched strip tag counts: {0} offsets, {1} byte counts")]
+    MismatchedStripCounts(usize, usize),
+
+    #[error("Strip decoded to {0} bytes, expected {1}")]
+    ShortStrip(usize, usize),
+
+    #[error("Failed to inflate strip: {0}")]
+    InflateFailed(std::io::Error),
+
+    #[error("Malformed LZW stream")]
+    MalformedLzw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        match self {
+            ByteOrder::Little => u16::from_le_bytes([bytes[0], bytes[1]]),
+            ByteOrder::Big => u16::from_be_bytes([bytes[0], bytes[1]]),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        match self {
+            ByteOrder::Little => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            ByteOrder::Big => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+struct IfdEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    value_offset: [u8; 4],
+}
+
+pub struct TiffImage {
+    width: u32,
+    height: u32,
+    pixel_array: PixelArray,
+}
+
+impl TiffImage {
+    pub fn new(filename: &Path) -> Result<TiffImage, Issue> {
+        let mut file = File::open(filename)?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+
+        if buffer.len() < 8 {
+            return Err(Issue::BadStruct("TIFF header".to_string(), 8 - buffer.len()));
+        }
+
+        let order = match &buffer[0..2] {
+            b"II" => ByteOrder::Little,
+            b"MM" => ByteOrder::Big,
+            other => return Err(Issue::BadByteOrder(other.to_vec())),
+        };
+
+        let magic = order.u16(&buffer[2..4]);
+        if magic != 42 {
+            return Err(Issue::BadMagic(magic));
+        }
+
+        let ifd_offset = order.u32(&buffer[4..8]) as usize;
+        let entries = Self::read_ifd(&buffer, ifd_offset, order)?;
+
+        let width = Self::tag_values(&buffer, &entries, 256, order)?[0];
+        let height = Self::tag_values(&buffer, &entries, 257, order)?[0];
+        let compression = Self::tag_values(&buffer, &entries, 259, order).map(|v| v[0]).unwrap_or(1);
+        let samples_per_pixel = Self::tag_values(&buffer, &entries, 277, order).map(|v| v[0]).unwrap_or(3) as usize;
+        let bits_per_sample = Self::tag_values(&buffer, &entries, 258, order).map(|v| v[0]).unwrap_or(8);
+        let rows_per_strip = Self::tag_values(&buffer, &entries, 278, order).unwrap_or(vec![height])[0];
+        let strip_offsets = Self::tag_values(&buffer, &entries, 273, order)?;
+        let strip_byte_counts = Self::tag_values(&buffer, &entries, 279, order)?;
+
+        // from_rgb_rows() indexes chunk[0..3] as RGB, so only 8-bit-per-sample, 3+ channel
+        // strips (RGB/RGBA) are supported; grayscale/palette TIFFs need a format this
+        // pipeline doesn't decode yet.
+        if bits_per_sample != 8 {
+            return Err(Issue::UnsupportedBitsPerSample(bits_per_sample));
+        }
+        if samples_per_pixel < 3 {
+            return Err(Issue::UnsupportedSamplesPerPixel(samples_per_pixel));
+        }
+
+        if strip_offsets.len() != strip_byte_counts.len() {
+            return Err(Issue::MismatchedStripCounts(strip_offsets.len(), strip_byte_counts.len()));
+        }
+
+        let stride = width as usize * samples_per_pixel;
+        let mut raw = Vec::with_capacity(stride * height as usize);
+
+        for (i, &offset) in strip_offsets.iter().enumerate() {
+            let byte_count = strip_byte_counts[i] as usize;
+            let strip_data = buffer.get(offset as usize..offset as usize + byte_count)
+                .ok_or_else(|| Issue::BadStruct("strip data".to_string(), byte_count))?;
+
+            let rows_remaining = height.saturating_sub(i as u32 * rows_per_strip);
+            let rows_in_strip = rows_per_strip.min(rows_remaining) as usize;
+            let expected_len = stride * rows_in_strip;
+
+            let decoded = match compression {
+                1 => strip_data.to_vec(),
+                5 => Self::lzw_decode(strip_data)?,
+                8 | 32946 => {
+                    let mut out = Vec::with_capacity(expected_len);
+                    ZlibDecoder::new(strip_data).read_to_end(&mut out).map_err(Issue::InflateFailed)?;
+                    out
+                },
+                32773 => Self::packbits_decode(strip_data),
+                other => return Err(Issue::UnsupportedCompression(other)),
+            };
+
+            if decoded.len() != expected_len {
+                return Err(Issue::ShortStrip(decoded.len(), expected_len));
+            }
+
+            raw.extend_from_slice(&decoded);
+        }
+
+        let pixel_array = PixelArray::from_rgb_rows(width as usize, height as usize, &raw, samples_per_pixel);
+
+        Ok(TiffImage { width, height, pixel_array })
+    }
+
+    fn read_ifd(buffer: &[u8], offset: usize, order: ByteOrder) -> Result<Vec<IfdEntry>, Issue> {
+        let count_bytes = buffer.get(offset..offset + 2)
+            .ok_or_else(|| Issue::BadStruct("IFD entry count".to_string(), offset + 2 - buffer.len()))?;
+        let count = order.u16(count_bytes) as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry_offset = offset + 2 + i * 12;
+            let raw = buffer.get(entry_offset..entry_offset + 12)
+                .ok_or_else(|| Issue::BadStruct("IFD entry".to_string(), entry_offset + 12 - buffer.len()))?;
+
+            entries.push(IfdEntry {
+                tag: order.u16(&raw[0..2]),
+                field_type: order.u16(&raw[2..4]),
+                count: order.u32(&raw[4..8]),
+                value_offset: [raw[8], raw[9], raw[10], raw[11]],
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn field_type_size(field_type: u16) -> usize {
+        match field_type {
+            1 | 2 | 6 | 7 => 1, // BYTE, ASCII, SBYTE, UNDEFINED
+            3 | 8 => 2,         // SHORT, SSHORT
+            4 | 9 => 4,         // LONG, SLONG
+            _ => 4,
+        }
+    }
+
+    /// Resolves a tag's values, following the offset to external file data when the values
+    /// don't fit in the 4-byte inline slot.
+    fn tag_values(buffer: &[u8], entries: &[IfdEntry], tag: u16, order: ByteOrder) -> Result<Vec<u32>, Issue> {
+        let entry = entries.iter().find(|e| e.tag == tag)
+            .ok_or(Issue::MissingTag(Self::tag_name(tag)))?;
+
+        let type_size = Self::field_type_size(entry.field_type);
+        let total_size = type_size * entry.count as usize;
+
+        let data: Vec<u8> = if total_size <= 4 {
+            entry.value_offset.to_vec()
+        } else {
+            let offset = order.u32(&entry.value_offset) as usize;
+            buffer.get(offset..offset + total_size)
+                .ok_or_else(|| Issue::BadStruct("tag data".to_string(), total_size))?
+                .to_vec()
+        };
+
+        let values = data.chunks(type_size.max(1))
+            .take(entry.count as usize)
+            .map(|chunk| match type_size {
+                1 => chunk[0] as u32,
+                2 => order.u16(chunk) as u32,
+                _ => order.u32(chunk),
+            })
+            .collect();
+
+        Ok(values)
+    }
+
+    fn tag_name(tag: u16) -> &'static str {
+        match tag {
+            256 => "ImageWidth",
+            257 => "ImageLength",
+            259 => "Compression",
+            273 => "StripOffsets",
+            277 => "SamplesPerPixel",
+            278 => "RowsPerStrip",
+            279 => "StripByteCounts",
+            _ => "UnknownTag",
+        }
+    }
+
+    fn packbits_decode(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+
+        while i < data.len() {
+            let n = data[i];
+            i += 1;
+
+            match n {
+                0..=127 => {
+                    let count = n as usize + 1;
+                    if let Some(chunk) = data.get(i..i + count) {
+                        out.extend_from_slice(chunk);
+                    }
+                    i += count;
+                },
+                129..=255 => {
+                    let count = 257 - n as usize;
+                    if let Some(&value) = data.get(i) {
+                        out.extend(std::iter::repeat_n(value, count));
+                    }
+                    i += 1;
+                },
+                128 => {}, // no-op
+            }
+        }
+
+        out
+    }
+
+    /// Decodes a baseline TIFF LZW strip: MSB-first bit packing, a 256-entry single-byte
+    /// seed dictionary plus ClearCode (256) and EOI (257), and "early change" code-width
+    /// growth (widened one code sooner than GIF's variant).
+    fn lzw_decode(data: &[u8]) -> Result<Vec<u8>, Issue> {
+        const CLEAR_CODE: u16 = 256;
+        const EOI_CODE: u16 = 257;
+
+        let mut out = Vec::new();
+        let mut bit_pos = 0usize;
+        let total_bits = data.len() * 8;
+
+        let mut dictionary: Vec<Vec<u8>> = Vec::new();
+        let mut code_width = 9u32;
+        let mut old_code: Option<u16> = None;
+
+        let reset_dictionary = |dictionary: &mut Vec<Vec<u8>>| {
+            dictionary.clear();
+            for b in 0..256u16 {
+                dictionary.push(vec![b as u8]);
+            }
+            dictionary.push(Vec::new()); // 256: ClearCode placeholder
+            dictionary.push(Vec::new()); // 257: EOI placeholder
+        };
+        reset_dictionary(&mut dictionary);
+
+        loop {
+            if bit_pos + code_width as usize > total_bits {
+                break;
+            }
+
+            let code = Self::read_code(data, bit_pos, code_width);
+            bit_pos += code_width as usize;
+
+            if code == CLEAR_CODE {
+                reset_dictionary(&mut dictionary);
+                code_width = 9;
+                old_code = None;
+                continue;
+            }
+
+            if code == EOI_CODE {
+                break;
+            }
+
+            let entry = if (code as usize) < dictionary.len() {
+                dictionary[code as usize].clone()
+            } else if code as usize == dictionary.len() {
+                let prev = old_code.ok_or(Issue::MalformedLzw)?;
+                let mut entry = dictionary[prev as usize].clone();
+                entry.push(dictionary[prev as usize][0]);
+                entry
+            } else {
+                return Err(Issue::MalformedLzw);
+            };
+
+            out.extend_from_slice(&entry);
+
+            if let Some(prev) = old_code {
+                let mut new_entry = dictionary[prev as usize].clone();
+                new_entry.push(entry[0]);
+                dictionary.push(new_entry);
+
+                match dictionary.len() {
+                    511 => code_width = 10,
+                    1023 => code_width = 11,
+                    2047 => code_width = 12,
+                    _ => {},
+                }
+            }
+
+            old_code = Some(code);
+        }
+
+        Ok(out)
+    }
+
+    fn read_code(data: &[u8], bit_pos: usize, width: u32) -> u16 {
+        let mut code: u16 = 0;
+        for i in 0..width {
+            let bit_index = bit_pos + i as usize;
+            let byte = data[bit_index / 8];
+            let bit = (byte >> (7 - (bit_index % 8))) & 1;
+            code = (code << 1) | bit as u16;
+        }
+        code
+    }
+
+    pub fn pixel_array(&self) -> &PixelArray {
+        &self.pixel_array
+    }
+
+    pub fn dither(&mut self, pallete: &crate::pixels::Pallete, nbits: i32, method: crate::pixels::DitherMethod) {
+        self.pixel_array.dither(pallete, nbits, method);
+    }
+
+    /// Writes a minimal baseline, uncompressed, single-strip TIFF: header, raw RGB pixel
+    /// data, then a single IFD.
+    pub fn save(&self, path: &Path) -> Result<usize, Issue> {
+        let mut file = File::create(path)?;
+
+        let mut pixel_data = Vec::with_capacity(self.width as usize * self.height as usize * 3);
+        for pixel in self.pixel_array.pixel_array(false) {
+            pixel_data.push(pixel.r);
+            pixel_data.push(pixel.g);
+            pixel_data.push(pixel.b);
+        }
+
+        let bits_per_sample_offset = 8 + pixel_data.len();
+        let mut bits_per_sample_data = Vec::new();
+        for _ in 0..3 {
+            bits_per_sample_data.extend_from_slice(&8u16.to_le_bytes());
+        }
+
+        let mut ifd_offset = bits_per_sample_offset + bits_per_sample_data.len();
+        let padding = ifd_offset % 2;
+        ifd_offset += padding;
+
+        let short_value = |v: u16| -> [u8; 4] {
+            let mut value = [0u8; 4];
+            value[0..2].copy_from_slice(&v.to_le_bytes());
+            value
+        };
+        let long_value = |v: u32| -> [u8; 4] { v.to_le_bytes() };
+
+        let entries: [(u16, u16, u32, [u8; 4]); 9] = [
+            (256, 3, 1, short_value(self.width as u16)),
+            (257, 3, 1, short_value(self.height as u16)),
+            (258, 3, 3, long_value(bits_per_sample_offset as u32)),
+            (259, 3, 1, short_value(1)), // Compression: none
+            (262, 3, 1, short_value(2)), // PhotometricInterpretation: RGB
+            (273, 4, 1, long_value(8)),  // StripOffsets
+            (277, 3, 1, short_value(3)), // SamplesPerPixel
+            (278, 4, 1, long_value(self.height)), // RowsPerStrip
+            (279, 4, 1, long_value(pixel_data.len() as u32)), // StripByteCounts
+        ];
+
+        let mut written = file.write(b"II")?;
+        written += file.write(&42u16.to_le_bytes())?;
+        written += file.write(&(ifd_offset as u32).to_le_bytes())?;
+
+        written += file.write(&pixel_data)?;
+        written += file.write(&bits_per_sample_data)?;
+        if padding > 0 {
+            written += file.write(&[0u8; 1])?;
+        }
+
+        written += file.write(&(entries.len() as u16).to_le_bytes())?;
+        for (tag, field_type, count, value) in entries {
+            written += file.write(&tag.to_le_bytes())?;
+            written += file.write(&field_type.to_le_bytes())?;
+            written += file.write(&count.to_le_bytes())?;
+            written += file.write(&value)?;
+        }
+        written += file.write(&0u32.to_le_bytes())?; // no next IFD
+
+        Ok(written)
+    }
+}